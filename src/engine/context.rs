@@ -1,6 +1,9 @@
 use engine::asset::AssetResult;
 use engine::core::Component;
 use engine::engine::EngineStats;
+use engine::render::post_process::PostProcessChain;
+use engine::render::render_queue::RenderQueue;
+use engine::render::shadow::{self, ShadowPass, ShadowSettings};
 use engine::render::{CullMode, DepthTest, Material, MaterialState, MeshBuffer, ShaderProgram,
                      Texture};
 use std::collections::VecDeque;
@@ -142,6 +145,13 @@ pub struct EngineContext {
     pub main_light: Option<Arc<Component>>,
     pub point_lights: Vec<Arc<Component>>,
 
+    /// Shadow settings for `main_light`; no effect while `main_light` is `None`.
+    pub main_light_shadow: ShadowSettings,
+    /// Shadow settings for `point_lights`, indexed in parallel with it.
+    pub point_light_shadows: Vec<ShadowSettings>,
+    /// The actual shadow-map render targets driven by `main_light_shadow`/`point_light_shadows`.
+    pub shadow_pass: ShadowPass,
+
     pub switch_mesh: u32,
     pub switch_prog: u32,
     pub switch_tex: u32,
@@ -151,6 +161,15 @@ pub struct EngineContext {
 
     pub last_light_bound: Option<Weak<ShaderProgram>>,
     pub last_material_bound: Option<Weak<Material>>,
+
+    /// Draw commands for the current frame, sorted and issued by `RenderQueue::flush` instead
+    /// of in raw submission order, so redundant program/mesh rebinds collapse.
+    pub render_queue: RenderQueue,
+
+    /// The scene's post-processing effect chain, if one has been set up (via `PostProcessChain`
+    /// plus `add_pass`, once the quad mesh and effect shaders are loaded). `None` until then;
+    /// `run_post_process` passes `scene_color` through unchanged while it is.
+    pub post_process: Option<PostProcessChain>,
 }
 
 impl EngineContext {
@@ -162,6 +181,9 @@ impl EngineContext {
 
             main_light: Default::default(),
             point_lights: Default::default(),
+            main_light_shadow: Default::default(),
+            point_light_shadows: Default::default(),
+            shadow_pass: ShadowPass::new(),
 
             switch_mesh: 0,
             switch_prog: 0,
@@ -172,8 +194,97 @@ impl EngineContext {
             states: Default::default(),
             last_light_bound: None,
             last_material_bound: None,
+
+            render_queue: RenderQueue::new(),
+            post_process: None,
+        }
+    }
+}
+
+impl EngineContext {
+    /// Re-syncs every shadow map against the current settings: allocates/frees the directional
+    /// map against `main_light_shadow`, and grows `point_light_shadows` (defaulted, disabled) up
+    /// to `point_lights.len()` before syncing each point light's map in turn. Call once per
+    /// frame, before `render_shadows`, so resolution/on-off changes take effect without a
+    /// restart and newly spawned point lights get a settings slot.
+    pub fn sync_shadows(&mut self) {
+        self.shadow_pass.sync_directional(&self.main_light_shadow);
+
+        while self.point_light_shadows.len() < self.point_lights.len() {
+            self.point_light_shadows.push(ShadowSettings::default());
+        }
+
+        for (i, settings) in self.point_light_shadows.iter().enumerate() {
+            self.shadow_pass.sync_point(i, settings);
         }
     }
+
+    /// Renders every allocated shadow map for this frame: the directional map from `light_dir`
+    /// (looking at `scene_center`, radius `scene_radius`), then each point light's cubemap from
+    /// `point_light_positions`, indexed in parallel with `point_lights`/`point_light_shadows`.
+    /// `draw_depth_only` is called once per map (six times for a point light, once per face)
+    /// with the map's own view-projection matrix; the caller supplies it since it alone knows
+    /// how to iterate the scene's shadow-casting meshes.
+    ///
+    /// Light positions/directions come from the caller rather than being read off
+    /// `main_light`/`point_lights` directly, since `Component` doesn't expose a transform
+    /// accessor this module can call.
+    pub fn render_shadows<F>(
+        &mut self,
+        gl: &WebGLRenderingContext,
+        light_dir: (f32, f32, f32),
+        scene_center: (f32, f32, f32),
+        scene_radius: f32,
+        point_light_positions: &[(f32, f32, f32)],
+        mut draw_depth_only: F,
+    ) where
+        F: FnMut(&WebGLRenderingContext, &[[f32; 4]; 4]),
+    {
+        let view_proj = shadow::directional_view_proj(light_dir, scene_center, scene_radius);
+        self.shadow_pass
+            .render_directional(gl, view_proj, &mut draw_depth_only);
+
+        for (i, &pos) in point_light_positions.iter().enumerate() {
+            let far = self.point_light_shadows
+                .get(i)
+                .map(|s| s.far)
+                .unwrap_or_else(|| ShadowSettings::default().far);
+            self.shadow_pass
+                .render_point(gl, i, pos, far, &mut draw_depth_only);
+        }
+    }
+
+    /// Sorts and issues every draw command queued this frame on `render_queue`, then clears it
+    /// for the next one. The queue is taken out of `self` for the duration of the flush since
+    /// `RenderQueue::flush` needs `&mut EngineContext` itself to run each command's bind closure
+    /// (the same reason `prepare_cache`'s `bind` closure takes `&mut EngineContext` rather than
+    /// being handed the cached field directly). Scene code populates the queue for the frame via
+    /// `ctx.render_queue.push_opaque`/`push_transparent` before calling this.
+    pub fn flush_render_queue(&mut self) -> AssetResult<()> {
+        let mut queue = ::std::mem::replace(&mut self.render_queue, RenderQueue::new());
+        let result = queue.flush(self);
+        self.render_queue = queue;
+        result
+    }
+
+    /// Runs `self.post_process` (if set up) over `scene_color` and returns its final output, or
+    /// `scene_color` unchanged if no chain has been set up yet. The chain is taken out of `self`
+    /// for the duration of the render since `PostProcessChain::render` needs `&mut EngineContext`
+    /// itself to route each pass's program bind through `prepare_cache`.
+    pub fn run_post_process(
+        &mut self,
+        gl: &WebGLRenderingContext,
+        scene_color: &Rc<Texture>,
+    ) -> AssetResult<Rc<Texture>> {
+        let mut chain = match self.post_process.take() {
+            Some(chain) => chain,
+            None => return Ok(scene_color.clone()),
+        };
+
+        let result = chain.render(self, gl, scene_color);
+        self.post_process = Some(chain);
+        result
+    }
 }
 
 macro_rules! impl_cacher {
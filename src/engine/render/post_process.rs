@@ -0,0 +1,281 @@
+use engine::asset::AssetResult;
+use engine::context::EngineContext;
+use engine::render::render_texture::RenderTexture;
+use engine::render::shader::{ShaderFs, ShaderVs};
+use engine::render::shader_link::{self, ShaderCacheConfig};
+use engine::render::{MeshBuffer, ShaderProgram, Texture, TextureAttachment, TextureFilter,
+                     TextureWrap};
+use std::collections::VecDeque;
+use std::rc::Rc;
+use uni_gl::WebGLRenderingContext;
+
+/// Vertex attributes every fullscreen quad mesh built for this chain supplies, used to validate
+/// each pass's vertex shader reflection at `add_pass` time.
+const QUAD_ATTRS: [&str; 2] = ["position", "uv"];
+
+/// How a pass sizes its target relative to the scene being processed.
+#[derive(Debug, Clone, Copy)]
+pub enum PassScale {
+    /// Scaled relative to the previous pass's output (or the scene color for the first pass).
+    Source(f32),
+    /// Scaled relative to the original scene color, regardless of where the pass sits in the chain.
+    Original(f32),
+    /// A fixed pixel size.
+    Absolute(u32, u32),
+}
+
+/// Number of previous final-frame colors kept around for `OriginalHistory0..N` samplers.
+const MAX_HISTORY_FRAMES: usize = 8;
+
+struct Pass {
+    scale: PassScale,
+    filter: TextureFilter,
+    wrap: TextureWrap,
+    format: TextureAttachment,
+    prog: Rc<ShaderProgram>,
+    feedback: bool,
+
+    target: RenderTexture,
+    // Only allocated when `feedback` is set: the previous frame's output for this pass,
+    // swapped with `target` every frame so the shader can sample its own last result.
+    feedback_target: Option<RenderTexture>,
+}
+
+fn resolve_size(scale: PassScale, original: (u32, u32), source: (u32, u32)) -> (u32, u32) {
+    match scale {
+        PassScale::Source(f) => (
+            (source.0 as f32 * f).round() as u32,
+            (source.1 as f32 * f).round() as u32,
+        ),
+        PassScale::Original(f) => (
+            (original.0 as f32 * f).round() as u32,
+            (original.1 as f32 * f).round() as u32,
+        ),
+        PassScale::Absolute(w, h) => (w, h),
+    }
+}
+
+impl Pass {
+    fn swap_feedback(&mut self) {
+        if let Some(ref mut fb) = self.feedback_target {
+            ::std::mem::swap(&mut self.target, fb);
+        }
+    }
+
+    /// Reallocates `target`/`feedback_target` for a new `(original, source)` size, e.g. after a
+    /// window resize. Uses the pass's own `scale`/`format` rather than the caller re-deriving
+    /// them, since those are exactly what `add_pass` used to size it the first time.
+    fn resize(&mut self, original_size: (u32, u32), source_size: (u32, u32)) {
+        let size = resolve_size(self.scale, original_size, source_size);
+        if size == self.target.size() {
+            return;
+        }
+
+        self.target = RenderTexture::new(size.0, size.1, self.format);
+        if self.feedback {
+            self.feedback_target = Some(RenderTexture::new(size.0, size.1, self.format));
+        }
+    }
+}
+
+/// Description of a single fullscreen pass, as parsed from a librashader/RetroArch-style preset.
+pub struct PassDesc {
+    pub scale: PassScale,
+    pub filter: TextureFilter,
+    pub wrap: TextureWrap,
+    pub format: TextureAttachment,
+    pub feedback: bool,
+}
+
+/// An ordered chain of fullscreen shader passes, each rendering into its own `RenderTexture`
+/// and feeding its output to the next pass as the `Source` sampler. Turns `RenderTexture` from
+/// a single offscreen target into a reusable effect graph (bloom, FXAA, CRT filters, ...).
+pub struct PostProcessChain {
+    quad: Rc<MeshBuffer>,
+    passes: Vec<Pass>,
+    history: VecDeque<Rc<Texture>>,
+    frame_count: u32,
+    /// Forwarded to `shader_link::link` for every pass compiled by `add_pass`; `None` skips the
+    /// on-disk program-binary cache entirely.
+    cache_cfg: Option<ShaderCacheConfig>,
+}
+
+impl PostProcessChain {
+    pub fn new(quad: Rc<MeshBuffer>) -> PostProcessChain {
+        PostProcessChain {
+            quad,
+            passes: Vec::new(),
+            history: VecDeque::with_capacity(MAX_HISTORY_FRAMES),
+            frame_count: 0,
+            cache_cfg: None,
+        }
+    }
+
+    /// Enables the on-disk program-binary cache for every pass this chain compiles from here on.
+    pub fn with_cache(mut self, cache_cfg: ShaderCacheConfig) -> PostProcessChain {
+        self.cache_cfg = Some(cache_cfg);
+        self
+    }
+
+    /// Appends a pass to the end of the chain: links `vs`/`fs` through `shader_link::link` (which
+    /// validates their reflection against the quad's known vertex layout and this pass's sampler
+    /// set — `Original`, `Source`, `OriginalHistory0..N`, and `Feedback` when `desc.feedback` is
+    /// set — before compiling anything, so a typo'd sampler name fails loudly here instead of
+    /// silently rendering black) and allocates its `RenderTexture` (and, for feedback passes, the
+    /// previous-frame framebuffer it is double-buffered against).
+    pub fn add_pass(
+        &mut self,
+        gl: &WebGLRenderingContext,
+        vs: &ShaderVs,
+        fs: &ShaderFs,
+        desc: PassDesc,
+        original_size: (u32, u32),
+    ) -> AssetResult<()> {
+        let mut bound_textures: Vec<String> = vec!["Original".to_string(), "Source".to_string()];
+        for i in 0..MAX_HISTORY_FRAMES {
+            bound_textures.push(format!("OriginalHistory{}", i));
+        }
+        if desc.feedback {
+            bound_textures.push("Feedback".to_string());
+        }
+
+        let vertex_layout_attrs: Vec<String> = QUAD_ATTRS.iter().map(|s| s.to_string()).collect();
+        let program = shader_link::link(
+            gl,
+            vs,
+            fs,
+            &vertex_layout_attrs,
+            &bound_textures,
+            self.cache_cfg.as_ref(),
+        )?;
+        let prog = Rc::new(ShaderProgram::new(gl, program));
+
+        let source_size = self.passes
+            .last()
+            .map(|p| p.target.size())
+            .unwrap_or(original_size);
+
+        let size = resolve_size(desc.scale, original_size, source_size);
+
+        let target = RenderTexture::new(size.0, size.1, desc.format);
+        let feedback_target = if desc.feedback {
+            Some(RenderTexture::new(size.0, size.1, desc.format))
+        } else {
+            None
+        };
+
+        self.passes.push(Pass {
+            scale: desc.scale,
+            filter: desc.filter,
+            wrap: desc.wrap,
+            format: desc.format,
+            feedback: desc.feedback,
+            prog,
+            target,
+            feedback_target,
+        });
+
+        Ok(())
+    }
+
+    /// Reallocates every pass's render targets for a new output size, e.g. on window resize.
+    pub fn resize(&mut self, original_size: (u32, u32)) {
+        let mut source_size = original_size;
+        for pass in self.passes.iter_mut() {
+            pass.resize(original_size, source_size);
+            source_size = pass.target.size();
+        }
+    }
+
+    /// Runs every pass in order, sampling `scene_color` as `Original`/`Source` for the first
+    /// pass and each pass's own output as `Source` for the next one. Each pass's `OutputSize`
+    /// uniform is its own render target's size (matching the librashader/RetroArch preset
+    /// semantics this chain follows), which only coincides with the final, on-screen size for
+    /// the last pass. Returns the final pass's color texture, or `scene_color` unchanged if the
+    /// chain has no passes.
+    ///
+    /// Binds each pass's program through `ctx.prepare_cache` rather than calling `pass.prog.bind`
+    /// directly, so `ctx.prog`/`ctx.switch_prog` reflect what is actually bound afterward — a
+    /// direct bind left `ctx.prog` stale, so the very next draw outside this chain could skip a
+    /// real program switch because its `Rc::ptr_eq` check still matched whatever was bound before
+    /// this chain ran.
+    pub fn render(
+        &mut self,
+        ctx: &mut EngineContext,
+        gl: &WebGLRenderingContext,
+        scene_color: &Rc<Texture>,
+    ) -> AssetResult<Rc<Texture>> {
+        self.frame_count = self.frame_count.wrapping_add(1);
+
+        let mut source = scene_color.clone();
+        let original = scene_color.clone();
+
+        for pass in self.passes.iter_mut() {
+            pass.swap_feedback();
+
+            pass.target.bind_frame_buffer(gl);
+            gl.viewport(0, 0, pass.target.size().0 as i32, pass.target.size().1 as i32);
+            ctx.prepare_cache(&pass.prog, |ctx| {
+                ctx.switch_prog += 1;
+                pass.prog.bind(gl);
+                Ok(())
+            })?;
+
+            pass.prog.set_uniform_vec2("OutputSize", pass.target.size());
+            pass.prog.set_uniform_vec2("SourceSize", source.size());
+            pass.prog.set_uniform_i32("FrameCount", self.frame_count as i32);
+
+            pass.prog.bind_texture("Original", &original, pass.filter, pass.wrap);
+            pass.prog.bind_texture("Source", &source, pass.filter, pass.wrap);
+
+            for (i, frame) in self.history.iter().enumerate() {
+                pass.prog
+                    .bind_texture(&format!("OriginalHistory{}", i), frame, pass.filter, pass.wrap);
+            }
+
+            if let Some(ref fb) = pass.feedback_target {
+                pass.prog
+                    .bind_texture("Feedback", &fb.as_texture(), pass.filter, pass.wrap);
+            }
+
+            self.quad.draw(gl);
+
+            pass.target.unbind_frame_buffer(gl);
+            source = pass.target.as_texture();
+        }
+
+        self.history.push_front(scene_color.clone());
+        self.history.truncate(MAX_HISTORY_FRAMES);
+
+        Ok(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_size_source_is_relative_to_previous_pass() {
+        assert_eq!(
+            resolve_size(PassScale::Source(0.5), (1920, 1080), (800, 600)),
+            (400, 300)
+        );
+    }
+
+    #[test]
+    fn resolve_size_original_ignores_previous_pass() {
+        assert_eq!(
+            resolve_size(PassScale::Original(0.5), (1920, 1080), (800, 600)),
+            (960, 540)
+        );
+    }
+
+    #[test]
+    fn resolve_size_absolute_ignores_both() {
+        assert_eq!(
+            resolve_size(PassScale::Absolute(64, 64), (1920, 1080), (800, 600)),
+            (64, 64)
+        );
+    }
+}
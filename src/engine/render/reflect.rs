@@ -0,0 +1,182 @@
+use engine::asset::{AssetError, AssetResult};
+use uni_glsl::parser::TranslationUnit;
+use uni_glsl::query::*;
+use uni_glsl::TypeQualifier;
+
+/// A declared `attribute`/`in` vertex input, as found by walking the shader's parsed
+/// translation unit.
+#[derive(Debug, Clone)]
+pub struct ShaderAttribute {
+    pub name: String,
+    pub type_name: String,
+    pub location: Option<u32>,
+}
+
+/// A declared `uniform`, including samplers (`is_sampler` is set for any `sampler*` type).
+#[derive(Debug, Clone)]
+pub struct ShaderUniform {
+    pub name: String,
+    pub type_name: String,
+    pub is_sampler: bool,
+}
+
+/// The attribute/uniform/sampler tables declared by one shader stage, so material setup can be
+/// driven by what the shader actually declares instead of hand-maintained binding code.
+#[derive(Debug, Clone, Default)]
+pub struct Reflection {
+    attributes: Vec<ShaderAttribute>,
+    uniforms: Vec<ShaderUniform>,
+}
+
+impl Reflection {
+    pub fn from_translation_unit(unit: &TranslationUnit) -> Reflection {
+        let mut attributes = Vec::new();
+        let mut uniforms = Vec::new();
+
+        for decl in unit.declarations() {
+            if let Some(q) = decl.is(TypeQualifier::Attribute) {
+                attributes.push(ShaderAttribute {
+                    name: decl.name().to_string(),
+                    type_name: q.type_name().to_string(),
+                    location: decl.layout_location(),
+                });
+            } else if let Some(q) = decl.is(TypeQualifier::Uniform) {
+                let type_name = q.type_name().to_string();
+                uniforms.push(ShaderUniform {
+                    is_sampler: type_name.starts_with("sampler"),
+                    name: decl.name().to_string(),
+                    type_name,
+                });
+            }
+        }
+
+        Reflection {
+            attributes,
+            uniforms,
+        }
+    }
+
+    pub fn attributes(&self) -> &[ShaderAttribute] {
+        &self.attributes
+    }
+
+    pub fn uniforms(&self) -> &[ShaderUniform] {
+        &self.uniforms
+    }
+
+    pub fn has_attr(&self, name: &str) -> bool {
+        self.attributes.iter().any(|a| a.name == name)
+    }
+
+    fn samplers(&self) -> impl Iterator<Item = &ShaderUniform> {
+        self.uniforms.iter().filter(|u| u.is_sampler)
+    }
+}
+
+/// Checked at link time: every active vertex attribute must be supplied by the mesh's vertex
+/// layout, and every sampler uniform must have a texture bound under that name. Returns a
+/// descriptive `AssetResult` error instead of letting the program silently render nothing.
+pub fn validate_bindings(
+    vs: &Reflection,
+    fs: &Reflection,
+    vertex_layout_attrs: &[String],
+    bound_textures: &[String],
+) -> AssetResult<()> {
+    for attr in vs.attributes() {
+        if !vertex_layout_attrs.iter().any(|a| a == &attr.name) {
+            return Err(AssetError::Other(format!(
+                "shader expects vertex attribute `{}` ({}) but the mesh buffer's layout does not supply it",
+                attr.name, attr.type_name
+            )));
+        }
+    }
+
+    for sampler in vs.samplers().chain(fs.samplers()) {
+        if !bound_textures.iter().any(|t| t == &sampler.name) {
+            return Err(AssetError::Other(format!(
+                "shader declares sampler uniform `{}` ({}) but no texture is bound to it",
+                sampler.name, sampler.type_name
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attr(name: &str) -> ShaderAttribute {
+        ShaderAttribute {
+            name: name.to_string(),
+            type_name: "vec3".to_string(),
+            location: None,
+        }
+    }
+
+    fn sampler(name: &str) -> ShaderUniform {
+        ShaderUniform {
+            name: name.to_string(),
+            type_name: "sampler2D".to_string(),
+            is_sampler: true,
+        }
+    }
+
+    #[test]
+    fn validate_bindings_passes_when_everything_is_supplied() {
+        let vs = Reflection {
+            attributes: vec![attr("position")],
+            uniforms: vec![sampler("Source")],
+        };
+        let fs = Reflection::default();
+
+        let result = validate_bindings(
+            &vs,
+            &fs,
+            &["position".to_string()],
+            &["Source".to_string()],
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn validate_bindings_rejects_missing_vertex_attribute() {
+        let vs = Reflection {
+            attributes: vec![attr("uv")],
+            uniforms: Vec::new(),
+        };
+        let fs = Reflection::default();
+
+        let result = validate_bindings(&vs, &fs, &["position".to_string()], &[]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn validate_bindings_rejects_unbound_sampler() {
+        let vs = Reflection::default();
+        let fs = Reflection {
+            attributes: Vec::new(),
+            uniforms: vec![sampler("Source")],
+        };
+
+        let result = validate_bindings(&vs, &fs, &[], &[]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn validate_bindings_ignores_non_sampler_uniforms() {
+        let vs = Reflection {
+            attributes: Vec::new(),
+            uniforms: vec![ShaderUniform {
+                name: "MVP".to_string(),
+                type_name: "mat4".to_string(),
+                is_sampler: false,
+            }],
+        };
+        let fs = Reflection::default();
+
+        let result = validate_bindings(&vs, &fs, &[], &[]);
+        assert!(result.is_ok());
+    }
+}
@@ -0,0 +1,161 @@
+use engine::asset::AssetResult;
+use engine::context::EngineContext;
+
+/// Bit layout of an opaque sort key, packed high-to-low so a plain numeric sort groups draws
+/// by program, then material, then mesh buffer: the cheapest-to-switch state changes last.
+/// 21 bits per field (63 total) leaves bit 63 exclusively for `TRANSPARENT_FLAG` so no opaque
+/// key can ever be misread as transparent.
+const OPAQUE_FIELD_BITS: u32 = 21;
+const OPAQUE_FIELD_MASK: u64 = (1 << OPAQUE_FIELD_BITS) - 1;
+const OPAQUE_PROG_SHIFT: u32 = 2 * OPAQUE_FIELD_BITS;
+const OPAQUE_MATERIAL_SHIFT: u32 = OPAQUE_FIELD_BITS;
+const OPAQUE_MESH_SHIFT: u32 = 0;
+
+/// Set on a transparent command's key so it always sorts after every opaque one; within the
+/// transparent range, the remaining bits hold an inverted camera distance for back-to-front
+/// order (farthest first).
+const TRANSPARENT_FLAG: u64 = 1 << 63;
+
+fn opaque_key(prog_id: u32, material_id: u32, mesh_id: u32) -> u64 {
+    ((prog_id as u64 & OPAQUE_FIELD_MASK) << OPAQUE_PROG_SHIFT)
+        | ((material_id as u64 & OPAQUE_FIELD_MASK) << OPAQUE_MATERIAL_SHIFT)
+        | ((mesh_id as u64 & OPAQUE_FIELD_MASK) << OPAQUE_MESH_SHIFT)
+}
+
+fn transparent_key(camera_distance: f32) -> u64 {
+    // Farthest first: invert so a larger distance produces a smaller key component, then sort
+    // ascending as usual.
+    let inverted = (!camera_distance.to_bits()) as u64;
+    TRANSPARENT_FLAG | inverted
+}
+
+/// The `(program id, material id, mesh buffer id)` an opaque command was keyed on, kept
+/// alongside the key so `flush` can count real state-switch transitions between consecutive
+/// draws instead of re-deriving them from the packed bits.
+type OpaqueIds = (u32, u32, u32);
+
+/// One queued draw: a packed sort key plus the closure that actually issues it once the queue
+/// has ordered commands to minimize state switches. Mirrors the bind-closure style already
+/// used by `EngineContext::prepare_cache`.
+struct DrawCommand {
+    key: u64,
+    ids: Option<OpaqueIds>,
+    bind: Box<FnMut(&mut EngineContext) -> AssetResult<()>>,
+}
+
+/// Accumulates draw commands for a frame and flushes them in an order that groups opaque
+/// geometry by program -> material -> mesh and draws transparent geometry afterward,
+/// back-to-front by camera distance. Owned by `EngineContext` and flushed once per frame by
+/// the caller that used to issue draws directly in submission order; relies on the existing
+/// `prepare_cache`/`prepare_cache_tex` paths in the bind closures to make the resulting state
+/// changes collapse naturally.
+#[derive(Default)]
+pub struct RenderQueue {
+    commands: Vec<DrawCommand>,
+}
+
+impl RenderQueue {
+    pub fn new() -> RenderQueue {
+        RenderQueue {
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn push_opaque<F>(&mut self, prog_id: u32, material_id: u32, mesh_id: u32, bind: F)
+    where
+        F: FnMut(&mut EngineContext) -> AssetResult<()> + 'static,
+    {
+        self.commands.push(DrawCommand {
+            key: opaque_key(prog_id, material_id, mesh_id),
+            ids: Some((prog_id, material_id, mesh_id)),
+            bind: Box::new(bind),
+        });
+    }
+
+    pub fn push_transparent<F>(&mut self, camera_distance: f32, bind: F)
+    where
+        F: FnMut(&mut EngineContext) -> AssetResult<()> + 'static,
+    {
+        self.commands.push(DrawCommand {
+            key: transparent_key(camera_distance),
+            ids: None,
+            bind: Box::new(bind),
+        });
+    }
+
+    /// Sorts the accumulated commands by key and issues them in order. `sort_by_key` is stable,
+    /// which keeps submission order as the tiebreaker within an identical program/material/mesh
+    /// bucket (or identical transparent depth). Each opaque command's ids are compared against
+    /// the previous opaque command's, so `ctx.switch_prog`/`ctx.switch_mesh` only tick up when
+    /// the sorted order actually changes program or mesh buffer, not once per draw.
+    pub fn flush(&mut self, ctx: &mut EngineContext) -> AssetResult<()> {
+        self.commands.sort_by_key(|c| c.key);
+
+        let mut last_ids: Option<OpaqueIds> = None;
+        for cmd in self.commands.iter_mut() {
+            if let Some((prog_id, _material_id, mesh_id)) = cmd.ids {
+                let (last_prog, last_mesh) = last_ids
+                    .map(|(p, _, m)| (Some(p), Some(m)))
+                    .unwrap_or((None, None));
+
+                if last_prog != Some(prog_id) {
+                    ctx.switch_prog += 1;
+                }
+                if last_mesh != Some(mesh_id) {
+                    ctx.switch_mesh += 1;
+                }
+
+                last_ids = cmd.ids;
+            }
+
+            (cmd.bind)(ctx)?;
+        }
+
+        self.commands.clear();
+
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opaque_key_sorts_by_program_then_material_then_mesh() {
+        assert!(opaque_key(1, 0, 0) > opaque_key(0, 99, 99));
+        assert!(opaque_key(0, 1, 0) > opaque_key(0, 0, 99));
+        assert!(opaque_key(0, 0, 1) > opaque_key(0, 0, 0));
+    }
+
+    #[test]
+    fn opaque_key_never_sets_the_transparent_flag() {
+        let max_field = (1u32 << OPAQUE_FIELD_BITS) - 1;
+        assert_eq!(opaque_key(max_field, max_field, max_field) & TRANSPARENT_FLAG, 0);
+    }
+
+    #[test]
+    fn transparent_key_always_sets_the_flag() {
+        assert_ne!(transparent_key(0.0) & TRANSPARENT_FLAG, 0);
+        assert_ne!(transparent_key(1000.0) & TRANSPARENT_FLAG, 0);
+    }
+
+    #[test]
+    fn transparent_key_sorts_farthest_first() {
+        let near = transparent_key(1.0);
+        let far = transparent_key(100.0);
+        assert!(far < near);
+    }
+
+    #[test]
+    fn transparent_keys_always_sort_after_opaque_keys() {
+        let max_field = (1u32 << OPAQUE_FIELD_BITS) - 1;
+        let opaque = opaque_key(max_field, max_field, max_field);
+        let transparent = transparent_key(0.0);
+        assert!(transparent > opaque);
+    }
+}
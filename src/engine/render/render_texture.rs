@@ -31,4 +31,8 @@ impl RenderTexture {
     pub fn as_texture(&self) -> Rc<Texture> {
         self.0.texture.clone()
     }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.0.width, self.0.height)
+    }
 }
@@ -1,10 +1,9 @@
+use uni_glsl::parser;
 use uni_glsl::preprocessor;
 use uni_glsl::preprocessor::PreprocessError;
 
-//use uni_glsl::parser;
-// use uni_glsl::TypeQualifier;
-// use uni_glsl::query::*;
-
+use engine::render::reflect::{Reflection, ShaderAttribute, ShaderUniform};
+use engine::render::shadow;
 use uni_gl;
 use std::collections::HashMap;
 use std::marker::PhantomData;
@@ -15,6 +14,17 @@ pub enum ShaderKind {
     Fragment,
 }
 
+impl ShaderKind {
+    /// Stable string tag mixed into the `shader_cache` key so a vertex and fragment stage with
+    /// identical source never collide.
+    fn as_str(&self) -> &'static str {
+        match self {
+            &ShaderKind::Vertex => "vs",
+            &ShaderKind::Fragment => "fs",
+        }
+    }
+}
+
 pub trait ShaderKindProvider {
     fn kind() -> ShaderKind;
 }
@@ -43,6 +53,12 @@ impl PreprocessedShaderCode {
         &self.0
     }
 
+    /// The `(stage tag, source)` pair fed into `shader_cache::cache_key` so that a linked
+    /// `ShaderProgram`'s on-disk binary is keyed on exactly what got compiled.
+    pub fn cache_part<'a>(&'a self, kind: ShaderKind) -> (&'static str, &'a str) {
+        (kind.as_str(), &self.0)
+    }
+
     pub fn new(
         kind: ShaderKind,
         s: &str,
@@ -85,43 +101,69 @@ impl PreprocessedShaderCode {
 pub struct Shader<T: ShaderKindProvider> {
     pub code: PreprocessedShaderCode,
     pub filename: String,
-    //unit: parser::TranslationUnit,
+    reflection: Reflection,
     phantom: PhantomData<*const T>,
 }
 
 pub type ShaderVs = Shader<ShaderKindVs>;
 pub type ShaderFs = Shader<ShaderKindFs>;
 
+fn reflect(code: &PreprocessedShaderCode) -> Reflection {
+    let unit = parser::parse(code.as_string());
+    Reflection::from_translation_unit(&unit)
+}
+
 impl<T> Shader<T>
 where
     T: ShaderKindProvider,
 {
     pub fn new(filename: &str, s: &str) -> Shader<T> {
-        let code = PreprocessedShaderCode::new(T::kind(), s, &HashMap::new()).unwrap();
+        // Every fragment shader gets `shadow_sampling.glsl` available via `#include`, whether or
+        // not it actually uses it; the preprocessor only pulls in an external file a source
+        // references by name, so a shader that doesn't need shadows pays nothing for this.
+        let external_files = match T::kind() {
+            ShaderKind::Fragment => shadow::shader_sampling_external_files(),
+            ShaderKind::Vertex => HashMap::new(),
+        };
+
+        let code = PreprocessedShaderCode::new(T::kind(), s, &external_files).unwrap();
+        let reflection = reflect(&code);
 
         Shader {
-            //unit: unit,
             filename: filename.to_string(),
             code,
+            reflection,
             phantom: PhantomData,
         }
     }
 
     pub fn from_preprocessed(filename: &str, code: PreprocessedShaderCode) -> Shader<T> {
         uni_gl::print(&format!("preprocessing {}...\n", filename));
+        let reflection = reflect(&code);
 
         Shader {
-            //unit: unit,
             filename: filename.to_string(),
             code,
+            reflection,
             phantom: PhantomData,
         }
     }
 
-    // pub fn has_attr(&self, s: &str) -> bool {
-    //     self.unit
-    //         .query_decl(s)
-    //         .is(TypeQualifier::Attribute)
-    //         .is_some()
-    // }
+    pub fn has_attr(&self, s: &str) -> bool {
+        self.reflection.has_attr(s)
+    }
+
+    pub fn attributes(&self) -> &[ShaderAttribute] {
+        self.reflection.attributes()
+    }
+
+    pub fn uniforms(&self) -> &[ShaderUniform] {
+        self.reflection.uniforms()
+    }
+
+    /// The full attribute/uniform table, for callers (like link-time validation) that need more
+    /// than the flat `attributes()`/`uniforms()` slices.
+    pub fn reflection(&self) -> &Reflection {
+        &self.reflection
+    }
 }
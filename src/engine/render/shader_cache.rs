@@ -0,0 +1,192 @@
+//! On-disk cache of linked `glGetProgramBinary` blobs, keyed on the preprocessed source so
+//! that a shader already seen on a previous run can skip recompilation at startup.
+#![cfg(feature = "program_binary_cache")]
+
+use blake3;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use uni_gl::{WebGLProgram, WebGLRenderingContext};
+
+/// `glGetProgramBinary`/`glProgramBinary` are an extension on GL ES (`OES_get_program_binary`)
+/// and never available at all on WebGL 1; check before touching either entry point so we skip
+/// the cache cleanly instead of producing driver errors.
+pub fn binary_cache_supported(gl: &WebGLRenderingContext) -> bool {
+    !uni_gl::IS_GL_ES || gl.get_extension("OES_get_program_binary").is_some()
+}
+
+/// Where cached program binaries are read from / written to. Configured once at startup;
+/// defaults to the relative path `shader_cache`, resolved against the current working directory.
+#[derive(Debug, Clone)]
+pub struct ShaderCacheConfig {
+    pub dir: PathBuf,
+}
+
+impl Default for ShaderCacheConfig {
+    fn default() -> ShaderCacheConfig {
+        ShaderCacheConfig {
+            dir: PathBuf::from("shader_cache"),
+        }
+    }
+}
+
+/// A `glProgramBinary`-compatible blob plus the GL-defined format enum it was produced with.
+pub struct CachedProgramBinary {
+    pub format: u32,
+    pub bytes: Vec<u8>,
+}
+
+/// Hashes everything that can make a compiled binary invalid on another machine: the final
+/// preprocessed source for every shader stage, the stage kinds, and the driver string
+/// (`RENDERER`/`VERSION`), since program binaries are not portable across GL implementations.
+pub fn cache_key(stage_sources: &[(&str, &str)], driver_string: &str) -> String {
+    let mut hasher = blake3::Hasher::new();
+    for &(kind, source) in stage_sources {
+        hasher.update(kind.as_bytes());
+        hasher.update(&[0u8]);
+        hasher.update(source.as_bytes());
+        hasher.update(&[0u8]);
+    }
+    hasher.update(driver_string.as_bytes());
+    hasher.finalize().to_hex().to_string()
+}
+
+pub fn driver_string(gl: &WebGLRenderingContext) -> String {
+    format!(
+        "{}|{}",
+        gl.get_parameter_string(uni_gl::Parameter::Renderer),
+        gl.get_parameter_string(uni_gl::Parameter::Version)
+    )
+}
+
+fn path_for(cfg: &ShaderCacheConfig, key: &str) -> PathBuf {
+    cfg.dir.join(format!("{}.bin", key))
+}
+
+/// Looks up a previously linked program binary. Returns `Ok(None)` on a clean cache miss;
+/// `Err` only for I/O failures worth logging (a file too short to hold the 4-byte format header
+/// is treated as a miss too; the blob itself is not validated here).
+pub fn load(cfg: &ShaderCacheConfig, key: &str) -> io::Result<Option<CachedProgramBinary>> {
+    let path = path_for(cfg, key);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let bytes = fs::read(&path)?;
+    if bytes.len() < 4 {
+        return Ok(None);
+    }
+
+    let (format_bytes, binary) = bytes.split_at(4);
+    let format = u32::from_le_bytes([format_bytes[0], format_bytes[1], format_bytes[2], format_bytes[3]]);
+
+    Ok(Some(CachedProgramBinary {
+        format,
+        bytes: binary.to_vec(),
+    }))
+}
+
+/// Persists a freshly linked program binary, overwriting any stale entry for this key.
+pub fn store(cfg: &ShaderCacheConfig, key: &str, binary: &CachedProgramBinary) -> io::Result<()> {
+    fs::create_dir_all(&cfg.dir)?;
+
+    let mut out = Vec::with_capacity(4 + binary.bytes.len());
+    out.extend_from_slice(&binary.format.to_le_bytes());
+    out.extend_from_slice(&binary.bytes);
+
+    write_atomic(&path_for(cfg, key), &out)
+}
+
+fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, bytes)?;
+    fs::rename(tmp, path)
+}
+
+/// Tries to load `program` (an already created program object) from the on-disk cache via
+/// `glProgramBinary`. Returns `Ok(true)` when the cached binary loaded and passes
+/// `GL_LINK_STATUS`.
+///
+/// Returns `Ok(false)` if the binary extension isn't available, or there's no cache entry yet,
+/// or `glProgramBinary` produces a program that fails `GL_LINK_STATUS` (a driver/version
+/// mismatch the hash didn't catch). This function neither links from source nor rewrites the
+/// entry; the caller does both, the latter via `store_after_link`.
+pub fn link_with_cache(
+    gl: &WebGLRenderingContext,
+    cfg: &ShaderCacheConfig,
+    key: &str,
+    program: &WebGLProgram,
+) -> io::Result<bool> {
+    if !binary_cache_supported(gl) {
+        return Ok(false);
+    }
+
+    let cached = match load(cfg, key)? {
+        Some(cached) => cached,
+        None => return Ok(false),
+    };
+
+    gl.program_binary(program, cached.format, &cached.bytes);
+
+    if gl.get_program_parameter_bool(program, uni_gl::ProgramParameter::LinkStatus) {
+        return Ok(true);
+    }
+
+    // Stale for this driver/version despite the hash matching (e.g. a driver update that kept
+    // the same RENDERER/VERSION string but changed its binary format); let the caller relink
+    // from source and we'll overwrite this entry via `store_after_link`.
+    Ok(false)
+}
+
+/// Called after a successful from-source link to populate (or refresh) the cache entry for
+/// `key`, so the next cold start can skip recompilation.
+pub fn store_after_link(
+    gl: &WebGLRenderingContext,
+    cfg: &ShaderCacheConfig,
+    key: &str,
+    program: &WebGLProgram,
+) -> io::Result<()> {
+    if !binary_cache_supported(gl) {
+        return Ok(());
+    }
+
+    let (format, bytes) = gl.get_program_binary(program);
+    store(cfg, key, &CachedProgramBinary { format, bytes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_key_is_stable_for_identical_input() {
+        let stages = [("vs", "void main() {}"), ("fs", "void main() {}")];
+        let a = cache_key(&stages, "driver-a");
+        let b = cache_key(&stages, "driver-a");
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn cache_key_differs_on_source_change() {
+        let a = cache_key(&[("vs", "void main() {}")], "driver-a");
+        let b = cache_key(&[("vs", "void main() { gl_Position = vec4(0); }")], "driver-a");
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn cache_key_differs_on_driver_change() {
+        let stages = [("vs", "void main() {}")];
+        let a = cache_key(&stages, "driver-a");
+        let b = cache_key(&stages, "driver-b");
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn cache_key_differs_on_stage_kind() {
+        // Same text, different stage tag: a vertex and fragment shader that happen to share
+        // source must not collide in the cache.
+        let a = cache_key(&[("vs", "void main() {}")], "driver-a");
+        let b = cache_key(&[("fs", "void main() {}")], "driver-a");
+        assert_ne!(a, b);
+    }
+}
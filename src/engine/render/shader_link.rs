@@ -0,0 +1,129 @@
+//! Links a vertex/fragment shader pair into a `WebGLProgram`: compiles both stages from source,
+//! attaches and links them, and — when the `program_binary_cache` feature is enabled — tries the
+//! on-disk `shader_cache` first so a shader already seen on a previous run skips recompilation.
+//! Returns the linked program; `ShaderProgram::new` owns everything beyond that (uniform
+//! locations, bound textures, ...), so this module has no need to know anything about
+//! `ShaderProgram`'s own fields.
+use engine::asset::{AssetError, AssetResult};
+use engine::render::reflect::validate_bindings;
+use engine::render::shader::{ShaderFs, ShaderKind, ShaderVs};
+#[cfg(feature = "program_binary_cache")]
+use engine::render::shader_cache;
+use uni_gl::{WebGLProgram, WebGLRenderingContext};
+
+/// Where cached program binaries live. Re-exported from `shader_cache` when the cache feature is
+/// on; otherwise a zero-sized stand-in so callers (like `PostProcessChain`) don't have to
+/// `#[cfg]` their own fields just to hold one around.
+#[cfg(feature = "program_binary_cache")]
+pub use engine::render::shader_cache::ShaderCacheConfig;
+
+#[cfg(not(feature = "program_binary_cache"))]
+#[derive(Debug, Clone, Default)]
+pub struct ShaderCacheConfig;
+
+fn compile_stage(
+    gl: &WebGLRenderingContext,
+    kind: ShaderKind,
+    source: &str,
+) -> AssetResult<uni_gl::WebGLShader> {
+    let gl_kind = match kind {
+        ShaderKind::Vertex => uni_gl::ShaderKind::Vertex,
+        ShaderKind::Fragment => uni_gl::ShaderKind::Fragment,
+    };
+
+    let shader = gl.create_shader(gl_kind);
+    gl.shader_source(&shader, source);
+    gl.compile_shader(&shader);
+
+    if !gl.get_shader_parameter_bool(&shader, uni_gl::ShaderParameter::CompileStatus) {
+        let log = gl.get_shader_info_log(&shader);
+        gl.delete_shader(&shader);
+        return Err(AssetError::Other(format!(
+            "failed to compile {:?} shader: {}",
+            kind, log
+        )));
+    }
+
+    Ok(shader)
+}
+
+/// Compiles `vs`/`fs` from source and links them. Never touches the on-disk cache; used both as
+/// the cache-miss fallback and directly when no `ShaderCacheConfig` is configured.
+fn link_from_source(
+    gl: &WebGLRenderingContext,
+    vs: &ShaderVs,
+    fs: &ShaderFs,
+) -> AssetResult<WebGLProgram> {
+    let vs_shader = compile_stage(gl, ShaderKind::Vertex, vs.code.as_string())?;
+    let fs_shader = compile_stage(gl, ShaderKind::Fragment, fs.code.as_string())?;
+
+    let program = gl.create_program();
+    gl.attach_shader(&program, &vs_shader);
+    gl.attach_shader(&program, &fs_shader);
+    gl.link_program(&program);
+
+    gl.delete_shader(&vs_shader);
+    gl.delete_shader(&fs_shader);
+
+    if !gl.get_program_parameter_bool(&program, uni_gl::ProgramParameter::LinkStatus) {
+        let log = gl.get_program_info_log(&program);
+        return Err(AssetError::Other(format!(
+            "failed to link shader program ({}+{}): {}",
+            vs.filename, fs.filename, log
+        )));
+    }
+
+    Ok(program)
+}
+
+/// Links `vs`/`fs` into a ready-to-use `WebGLProgram`. This is the one place every
+/// `ShaderProgram` in the engine gets built from its shader pair (post-process passes and
+/// materials alike), so it's also where binding validation lives: `vertex_layout_attrs`/
+/// `bound_textures` are checked against `vs`/`fs`'s reflection via `validate_bindings` before
+/// anything is compiled, so a typo'd attribute or sampler name fails loudly here instead of
+/// rendering black. When `cache_cfg` is given (and the `program_binary_cache` feature is on),
+/// tries `shader_cache::link_with_cache` first and falls back to compiling from source on a
+/// miss, storing the fresh binary back via `shader_cache::store_after_link` so the next run can
+/// skip recompilation.
+pub fn link(
+    gl: &WebGLRenderingContext,
+    vs: &ShaderVs,
+    fs: &ShaderFs,
+    vertex_layout_attrs: &[String],
+    bound_textures: &[String],
+    cache_cfg: Option<&ShaderCacheConfig>,
+) -> AssetResult<WebGLProgram> {
+    validate_bindings(vs.reflection(), fs.reflection(), vertex_layout_attrs, bound_textures)?;
+
+    #[cfg(feature = "program_binary_cache")]
+    {
+        if let Some(cfg) = cache_cfg {
+            let key = shader_cache::cache_key(
+                &[
+                    vs.code.cache_part(ShaderKind::Vertex),
+                    fs.code.cache_part(ShaderKind::Fragment),
+                ],
+                &shader_cache::driver_string(gl),
+            );
+
+            let program = gl.create_program();
+            let cached = shader_cache::link_with_cache(gl, cfg, &key, &program)
+                .map_err(|e| AssetError::Other(format!("shader cache read failed: {}", e)))?;
+
+            if cached {
+                return Ok(program);
+            }
+
+            let program = link_from_source(gl, vs, fs)?;
+            shader_cache::store_after_link(gl, cfg, &key, &program)
+                .map_err(|e| AssetError::Other(format!("shader cache write failed: {}", e)))?;
+
+            return Ok(program);
+        }
+    }
+
+    #[cfg(not(feature = "program_binary_cache"))]
+    let _ = cache_cfg;
+
+    link_from_source(gl, vs, fs)
+}
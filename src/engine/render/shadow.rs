@@ -0,0 +1,582 @@
+use engine::render::render_texture::RenderTexture;
+use engine::render::TextureAttachment;
+use std::collections::HashMap;
+use uni_gl::WebGLRenderingContext;
+
+/// Poisson-disc taps used by the PCF/PCSS filters, precomputed once rather than sampled at
+/// runtime. 16 taps is the usual sweet spot between banding and noise for a rotated disc.
+const POISSON_DISC_16: [(f32, f32); 16] = [
+    (-0.942_016_24, -0.399_062_16),
+    (0.945_586_1, -0.768_907_35),
+    (-0.094_184_1, -0.929_388_7),
+    (0.344_959_3, 0.293_877_8),
+    (-0.915_885_4, 0.457_137_3),
+    (-0.815_018_1, -0.876_595_4),
+    (-0.382_775_94, 0.276_768_5),
+    (0.974_844_4, 0.756_035_9),
+    (0.443_233_8, -0.975_388_5),
+    (0.537_431_9, 0.473_734_2),
+    (-0.264_969_1, -0.418_930_3),
+    (0.791_975_14, -0.096_407_1),
+    (-0.243_961_9, 0.998_415_4),
+    (0.346_811_5, -0.153_659_6),
+    (0.199_841_8, 0.786_643_4),
+    (-0.614_203_4, 0.386_396_5),
+];
+
+/// Filtering applied when comparing a fragment's light-space depth against the shadow map.
+#[derive(Debug, Clone, Copy)]
+pub enum ShadowFilter {
+    /// A single depth comparison against one shadow-map sample, done in the shader on a plain
+    /// `sampler2D`; no hardware comparison sampling and no filtering across texels.
+    Hard,
+    /// Percentage-Closer Filtering: averages `taps` comparisons over the Poisson disc,
+    /// rotating it per-fragment (by a pseudo-random angle derived from screen position) when
+    /// `rotate` is set, trading banding for noise.
+    Pcf { taps: u32, rotate: bool },
+    /// Percentage-Closer Soft Shadows: a blocker search over `search_taps` estimates the
+    /// penumbra from the average blocker-to-receiver distance, then scales a PCF radius
+    /// derived from `light_size` accordingly.
+    Pcss { search_taps: u32, light_size: f32 },
+}
+
+impl Default for ShadowFilter {
+    fn default() -> ShadowFilter {
+        ShadowFilter::Pcf {
+            taps: 16,
+            rotate: true,
+        }
+    }
+}
+
+/// Per-light shadow configuration.
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowSettings {
+    pub enabled: bool,
+    pub resolution: u32,
+    pub filter: ShadowFilter,
+    /// Constant depth bias, applied along the light direction to kill acne.
+    pub bias: f32,
+    /// Bias scaled by the surface's slope relative to the light; the larger of this slope term
+    /// and `bias` is applied.
+    pub slope_bias: f32,
+    /// Far plane for a point light's cubemap projection (directional lights use an
+    /// orthographic frustum sized from the scene bounds passed to `directional_view_proj`).
+    pub far: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> ShadowSettings {
+        ShadowSettings {
+            enabled: false,
+            resolution: 1024,
+            filter: ShadowFilter::default(),
+            bias: 0.002,
+            slope_bias: 0.01,
+            far: 25.0,
+        }
+    }
+}
+
+/// A depth-only `RenderTexture` holding one light's view of the scene, plus the light-space
+/// view-projection matrix the main pass needs to transform fragments into it.
+pub struct ShadowMap {
+    pub depth: RenderTexture,
+    pub view_proj: [[f32; 4]; 4],
+}
+
+impl ShadowMap {
+    fn new(resolution: u32) -> ShadowMap {
+        ShadowMap {
+            depth: RenderTexture::new(resolution, resolution, TextureAttachment::Depth),
+            view_proj: identity_matrix(),
+        }
+    }
+}
+
+fn identity_matrix() -> [[f32; 4]; 4] {
+    [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
+
+fn mat4_mul(a: &[[f32; 4]; 4], b: &[[f32; 4]; 4]) -> [[f32; 4]; 4] {
+    let mut out = [[0.0f32; 4]; 4];
+    for col in 0..4 {
+        for row in 0..4 {
+            out[col][row] = (0..4).map(|k| a[k][row] * b[col][k]).sum();
+        }
+    }
+    out
+}
+
+fn vec3_sub(a: (f32, f32, f32), b: (f32, f32, f32)) -> (f32, f32, f32) {
+    (a.0 - b.0, a.1 - b.1, a.2 - b.2)
+}
+
+fn vec3_cross(a: (f32, f32, f32), b: (f32, f32, f32)) -> (f32, f32, f32) {
+    (
+        a.1 * b.2 - a.2 * b.1,
+        a.2 * b.0 - a.0 * b.2,
+        a.0 * b.1 - a.1 * b.0,
+    )
+}
+
+fn vec3_dot(a: (f32, f32, f32), b: (f32, f32, f32)) -> f32 {
+    a.0 * b.0 + a.1 * b.1 + a.2 * b.2
+}
+
+fn vec3_normalize(a: (f32, f32, f32)) -> (f32, f32, f32) {
+    let len = vec3_dot(a, a).sqrt();
+    if len <= ::std::f32::EPSILON {
+        (0.0, 0.0, 0.0)
+    } else {
+        (a.0 / len, a.1 / len, a.2 / len)
+    }
+}
+
+/// Column-major right-handed look-at matrix, matching the convention the rest of the renderer
+/// already uses for view matrices.
+fn look_at(eye: (f32, f32, f32), center: (f32, f32, f32), up: (f32, f32, f32)) -> [[f32; 4]; 4] {
+    let f = vec3_normalize(vec3_sub(center, eye));
+    let s = vec3_normalize(vec3_cross(f, up));
+    let u = vec3_cross(s, f);
+
+    [
+        [s.0, u.0, -f.0, 0.0],
+        [s.1, u.1, -f.1, 0.0],
+        [s.2, u.2, -f.2, 0.0],
+        [-vec3_dot(s, eye), -vec3_dot(u, eye), vec3_dot(f, eye), 1.0],
+    ]
+}
+
+/// Column-major right-handed orthographic projection, clip space `z` in `[-1, 1]`.
+fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> [[f32; 4]; 4] {
+    [
+        [2.0 / (right - left), 0.0, 0.0, 0.0],
+        [0.0, 2.0 / (top - bottom), 0.0, 0.0],
+        [0.0, 0.0, -2.0 / (far - near), 0.0],
+        [
+            -(right + left) / (right - left),
+            -(top + bottom) / (top - bottom),
+            -(far + near) / (far - near),
+            1.0,
+        ],
+    ]
+}
+
+/// Column-major right-handed perspective projection with a square aspect ratio, used for each
+/// cubemap face of a point-light shadow.
+fn perspective_square(fovy_radians: f32, near: f32, far: f32) -> [[f32; 4]; 4] {
+    let f = 1.0 / (fovy_radians / 2.0).tan();
+    [
+        [f, 0.0, 0.0, 0.0],
+        [0.0, f, 0.0, 0.0],
+        [0.0, 0.0, (far + near) / (near - far), -1.0],
+        [0.0, 0.0, (2.0 * far * near) / (near - far), 0.0],
+    ]
+}
+
+/// Builds the view-projection matrix for the directional `main_light`: an orthographic frustum
+/// tight around a sphere (`scene_center`, `scene_radius`) that bounds the shadow-casting scene.
+pub fn directional_view_proj(
+    light_dir: (f32, f32, f32),
+    scene_center: (f32, f32, f32),
+    scene_radius: f32,
+) -> [[f32; 4]; 4] {
+    let dir = vec3_normalize(light_dir);
+    let eye = (
+        scene_center.0 - dir.0 * scene_radius,
+        scene_center.1 - dir.1 * scene_radius,
+        scene_center.2 - dir.2 * scene_radius,
+    );
+    let up = if dir.1.abs() > 0.99 {
+        (0.0, 0.0, 1.0)
+    } else {
+        (0.0, 1.0, 0.0)
+    };
+
+    let view = look_at(eye, scene_center, up);
+    let proj = orthographic(
+        -scene_radius,
+        scene_radius,
+        -scene_radius,
+        scene_radius,
+        0.0,
+        2.0 * scene_radius,
+    );
+
+    mat4_mul(&proj, &view)
+}
+
+/// The view direction and up vector for each face of a point-light shadow cubemap, in the
+/// standard GL cubemap face order (+X, -X, +Y, -Y, +Z, -Z).
+const CUBE_FACE_DIRS: [((f32, f32, f32), (f32, f32, f32)); 6] = [
+    ((1.0, 0.0, 0.0), (0.0, -1.0, 0.0)),
+    ((-1.0, 0.0, 0.0), (0.0, -1.0, 0.0)),
+    ((0.0, 1.0, 0.0), (0.0, 0.0, 1.0)),
+    ((0.0, -1.0, 0.0), (0.0, 0.0, -1.0)),
+    ((0.0, 0.0, 1.0), (0.0, -1.0, 0.0)),
+    ((0.0, 0.0, -1.0), (0.0, -1.0, 0.0)),
+];
+
+/// Builds the view-projection matrix for one face of a point light's shadow cubemap.
+pub fn point_face_view_proj(
+    light_pos: (f32, f32, f32),
+    face: usize,
+    far: f32,
+) -> [[f32; 4]; 4] {
+    let (dir, up) = CUBE_FACE_DIRS[face];
+    let center = (light_pos.0 + dir.0, light_pos.1 + dir.1, light_pos.2 + dir.2);
+    let view = look_at(light_pos, center, up);
+    let proj = perspective_square(::std::f32::consts::FRAC_PI_2, 0.05, far);
+
+    mat4_mul(&proj, &view)
+}
+
+/// A point light's shadow: six perspective depth maps rendered from the light's position, one
+/// per cubemap face. `SHADOW_SAMPLING_GLSL` has no lookup for these faces; it samples a single
+/// 2D map (`u_shadowMap`).
+pub struct PointShadowMap {
+    pub faces: [ShadowMap; 6],
+}
+
+impl PointShadowMap {
+    fn new(resolution: u32) -> PointShadowMap {
+        PointShadowMap {
+            faces: [
+                ShadowMap::new(resolution),
+                ShadowMap::new(resolution),
+                ShadowMap::new(resolution),
+                ShadowMap::new(resolution),
+                ShadowMap::new(resolution),
+                ShadowMap::new(resolution),
+            ],
+        }
+    }
+}
+
+/// Owns the shadow-casting render targets for the scene's lights and drives the depth-only
+/// pass that fills them in, ahead of the main material pass sampling them back.
+pub struct ShadowPass {
+    directional: Option<ShadowMap>,
+    points: Vec<Option<PointShadowMap>>,
+}
+
+impl ShadowPass {
+    pub fn new() -> ShadowPass {
+        ShadowPass {
+            directional: None,
+            points: Vec::new(),
+        }
+    }
+
+    /// (Re)allocates the directional shadow map if settings call for one, dropping it
+    /// otherwise. Called once per frame before rendering so resolution/on-off changes made by
+    /// the user take effect without a restart.
+    pub fn sync_directional(&mut self, settings: &ShadowSettings) {
+        match (settings.enabled, &self.directional) {
+            (false, _) => self.directional = None,
+            (true, Some(map)) if map.depth.size().0 == settings.resolution => {}
+            (true, _) => self.directional = Some(ShadowMap::new(settings.resolution)),
+        }
+    }
+
+    /// Same as `sync_directional`, but for the `index`-th point light; grows `points` lazily
+    /// as new point lights are added, and only allocates the (6 depth-texture) cubemap once
+    /// `settings.enabled` is actually set, freeing it again the moment it's turned off.
+    pub fn sync_point(&mut self, index: usize, settings: &ShadowSettings) {
+        while self.points.len() <= index {
+            self.points.push(None);
+        }
+
+        match (settings.enabled, &self.points[index]) {
+            (false, _) => self.points[index] = None,
+            (true, Some(map)) if map.faces[0].depth.size().0 == settings.resolution => {}
+            (true, _) => self.points[index] = Some(PointShadowMap::new(settings.resolution)),
+        }
+    }
+
+    pub fn directional_map(&self) -> Option<&ShadowMap> {
+        self.directional.as_ref()
+    }
+
+    pub fn point_map(&self, index: usize) -> Option<&PointShadowMap> {
+        self.points.get(index).and_then(|m| m.as_ref())
+    }
+
+    /// Renders the directional depth pass: binds the shadow map's framebuffer, updates its
+    /// `view_proj`, and lets `draw_depth_only` issue depth-only draws of every shadow-casting
+    /// mesh against `view_proj`. No-op while the directional shadow map isn't allocated
+    /// (`settings.enabled == false`, after `sync_directional`).
+    pub fn render_directional<F>(
+        &mut self,
+        gl: &WebGLRenderingContext,
+        view_proj: [[f32; 4]; 4],
+        mut draw_depth_only: F,
+    ) where
+        F: FnMut(&WebGLRenderingContext, &[[f32; 4]; 4]),
+    {
+        let map = match self.directional {
+            Some(ref mut map) => map,
+            None => return,
+        };
+
+        map.view_proj = view_proj;
+
+        let (w, h) = map.depth.size();
+        map.depth.bind_frame_buffer(gl);
+        gl.viewport(0, 0, w as i32, h as i32);
+        gl.clear(uni_gl::BufferBit::Depth as i32);
+
+        draw_depth_only(gl, &map.view_proj);
+
+        map.depth.unbind_frame_buffer(gl);
+    }
+
+    /// Renders all six cubemap faces for the `index`-th point light's shadow, in the same
+    /// depth-only fashion as `render_directional`. No-op while that light's shadow map isn't
+    /// allocated.
+    pub fn render_point<F>(
+        &mut self,
+        gl: &WebGLRenderingContext,
+        index: usize,
+        light_pos: (f32, f32, f32),
+        far: f32,
+        mut draw_depth_only: F,
+    ) where
+        F: FnMut(&WebGLRenderingContext, &[[f32; 4]; 4]),
+    {
+        let map = match self.points.get_mut(index).and_then(|m| m.as_mut()) {
+            Some(map) => map,
+            None => return,
+        };
+
+        for face in 0..6 {
+            let view_proj = point_face_view_proj(light_pos, face, far);
+            map.faces[face].view_proj = view_proj;
+
+            let (w, h) = map.faces[face].depth.size();
+            map.faces[face].depth.bind_frame_buffer(gl);
+            gl.viewport(0, 0, w as i32, h as i32);
+            gl.clear(uni_gl::BufferBit::Depth as i32);
+
+            draw_depth_only(gl, &view_proj);
+
+            map.faces[face].depth.unbind_frame_buffer(gl);
+        }
+    }
+}
+
+/// The Poisson disc shared by every PCF/PCSS-filtered light, exposed so the main pass can
+/// upload it once as a uniform array rather than re-deriving it per light.
+pub fn poisson_disc_16() -> &'static [(f32, f32); 16] {
+    &POISSON_DISC_16
+}
+
+/// GLSL implementing the filters described by `ShadowFilter`, meant to be spliced into the
+/// main fragment shader's preprocessing (as an external file handed to
+/// `PreprocessedShaderCode::new`) under the name `shadow_sampling.glsl`. `u_shadowFilter`
+/// selects hard (0), PCF (1), or PCSS (2) at runtime so one material shader variant covers all
+/// three without recompiling.
+///
+/// `texture()`/`textureSize()` are only available under desktop GLSL or `#version 300 es`; a
+/// plain GLSL ES 100 fragment shader (the engine's default on ES when a source doesn't opt into
+/// `#define USE_GLSL_300ES`) only has `texture2D()` and no `textureSize()` at all. `SHADOW_TEX`
+/// picks the right sampling call per profile, and the texel size comes from `u_shadowMapTexel`
+/// (set by the caller from the shadow map's resolution) instead of querying it in-shader.
+pub const SHADOW_SAMPLING_GLSL: &str = r#"
+#ifdef GL_ES
+  #ifdef USE_GLSL_300ES
+    #define SHADOW_TEX(tex, uv) texture(tex, uv)
+  #else
+    #define SHADOW_TEX(tex, uv) texture2D(tex, uv)
+  #endif
+#else
+  #define SHADOW_TEX(tex, uv) texture(tex, uv)
+#endif
+
+uniform sampler2D u_shadowMap;
+uniform float u_shadowBias;
+uniform float u_shadowSlopeBias;
+uniform int u_shadowFilter;
+uniform int u_shadowTaps;
+uniform bool u_shadowRotate;
+uniform float u_shadowLightSize;
+uniform float u_shadowMapTexel;
+uniform vec2 u_poissonDisc[16];
+
+float shadow_bias(vec3 normal, vec3 lightDir) {
+    return max(u_shadowSlopeBias * (1.0 - dot(normal, lightDir)), u_shadowBias);
+}
+
+float shadow_compare(vec2 uv, float receiverDepth, float bias) {
+    float occluderDepth = SHADOW_TEX(u_shadowMap, uv).r;
+    return receiverDepth - bias > occluderDepth ? 0.0 : 1.0;
+}
+
+float shadow_pcf(vec3 proj, float bias, float rotation) {
+    float s = sin(rotation);
+    float c = cos(rotation);
+    float visibility = 0.0;
+    float texel = u_shadowMapTexel;
+
+    for (int i = 0; i < u_shadowTaps; i++) {
+        vec2 offset = u_poissonDisc[i];
+        vec2 rotated = vec2(offset.x * c - offset.y * s, offset.x * s + offset.y * c);
+        visibility += shadow_compare(proj.xy + rotated * texel, proj.z, bias);
+    }
+
+    return visibility / float(u_shadowTaps);
+}
+
+float shadow_blocker_search(vec3 proj, float searchRadius, out int count) {
+    float avgBlocker = 0.0;
+    count = 0;
+
+    for (int i = 0; i < u_shadowTaps; i++) {
+        vec2 uv = proj.xy + u_poissonDisc[i] * searchRadius;
+        float occluderDepth = SHADOW_TEX(u_shadowMap, uv).r;
+        if (occluderDepth < proj.z) {
+            avgBlocker += occluderDepth;
+            count += 1;
+        }
+    }
+
+    return count > 0 ? avgBlocker / float(count) : 0.0;
+}
+
+float shadow_pcss(vec3 proj, float bias) {
+    int blockerCount;
+    float avgBlocker = shadow_blocker_search(proj, u_shadowLightSize, blockerCount);
+    if (blockerCount == 0) {
+        return 1.0;
+    }
+
+    float penumbra = u_shadowLightSize * (proj.z - avgBlocker) / avgBlocker;
+    float visibility = 0.0;
+
+    for (int i = 0; i < u_shadowTaps; i++) {
+        vec2 uv = proj.xy + u_poissonDisc[i] * penumbra;
+        visibility += shadow_compare(uv, proj.z, bias);
+    }
+
+    return visibility / float(u_shadowTaps);
+}
+
+// `lightSpacePos` is the fragment transformed by the light's view-projection matrix
+// (`ShadowMap::view_proj`); `normal`/`lightDir` are world-space and already normalized.
+float sample_shadow(vec4 lightSpacePos, vec3 normal, vec3 lightDir) {
+    vec3 proj = lightSpacePos.xyz / lightSpacePos.w;
+    proj = proj * 0.5 + 0.5;
+    if (proj.z > 1.0) {
+        return 1.0;
+    }
+
+    float bias = shadow_bias(normal, lightDir);
+
+    if (u_shadowFilter == 0) {
+        return shadow_compare(proj.xy, proj.z, bias);
+    } else if (u_shadowFilter == 1) {
+        float rotation = u_shadowRotate
+            ? fract(sin(dot(gl_FragCoord.xy, vec2(12.9898, 78.233))) * 43758.5453) * 6.2831853
+            : 0.0;
+        return shadow_pcf(proj, bias, rotation);
+    } else {
+        return shadow_pcss(proj, bias);
+    }
+}
+"#;
+
+/// Estimated penumbra radius (in light-space texels) for PCSS, given the average
+/// blocker-to-receiver distance found by the blocker search and the receiver's distance to
+/// the light, following the standard similar-triangles PCSS derivation.
+pub fn pcss_penumbra_size(light_size: f32, receiver_depth: f32, avg_blocker_depth: f32) -> f32 {
+    if avg_blocker_depth <= 0.0 {
+        return 0.0;
+    }
+
+    light_size * (receiver_depth - avg_blocker_depth) / avg_blocker_depth
+}
+
+/// `SHADOW_SAMPLING_GLSL`, keyed the way `PreprocessedShaderCode::new`'s `external_files` map
+/// expects, so a fragment shader can pull it in with `#include "shadow_sampling.glsl"`. Merged
+/// into every fragment shader's external files by `Shader::new`.
+pub fn shader_sampling_external_files() -> HashMap<String, String> {
+    let mut files = HashMap::new();
+    files.insert(
+        "shadow_sampling.glsl".to_string(),
+        SHADOW_SAMPLING_GLSL.to_string(),
+    );
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_eq(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn look_at_places_forward_axis_toward_center() {
+        let view = look_at((0.0, 0.0, 5.0), (0.0, 0.0, 0.0), (0.0, 1.0, 0.0));
+        // Looking down -Z toward the origin from (0,0,5): the eye transforms to the origin.
+        let eye_view = (
+            view[0][0] * 0.0 + view[1][0] * 0.0 + view[2][0] * 5.0 + view[3][0],
+            view[0][1] * 0.0 + view[1][1] * 0.0 + view[2][1] * 5.0 + view[3][1],
+            view[0][2] * 0.0 + view[1][2] * 0.0 + view[2][2] * 5.0 + view[3][2],
+        );
+        approx_eq(eye_view.0, 0.0);
+        approx_eq(eye_view.1, 0.0);
+        approx_eq(eye_view.2, 0.0);
+        // The center (the origin) lands straight ahead, on the view-space -Z axis.
+        approx_eq(view[3][0], 0.0);
+        approx_eq(view[3][1], 0.0);
+        approx_eq(view[3][2], -5.0);
+    }
+
+    #[test]
+    fn orthographic_maps_frustum_corners_to_clip_cube() {
+        let proj = orthographic(-1.0, 1.0, -1.0, 1.0, 0.0, 2.0);
+        // x = right, y = top, z = -far (right-handed: the view looks down -Z) should all land
+        // on the +1 clip-space edge.
+        approx_eq(proj[0][0] * 1.0 + proj[3][0], 1.0);
+        approx_eq(proj[1][1] * 1.0 + proj[3][1], 1.0);
+        approx_eq(proj[2][2] * -2.0 + proj[3][2], 1.0);
+    }
+
+    #[test]
+    fn mat4_mul_identity_is_a_no_op() {
+        let id = identity_matrix();
+        let m = orthographic(-1.0, 1.0, -1.0, 1.0, 0.0, 2.0);
+        let result = mat4_mul(&id, &m);
+        for col in 0..4 {
+            for row in 0..4 {
+                approx_eq(result[col][row], m[col][row]);
+            }
+        }
+    }
+
+    #[test]
+    fn point_face_view_proj_differs_per_face() {
+        let light_pos = (1.0, 2.0, 3.0);
+        let a = point_face_view_proj(light_pos, 0, 25.0);
+        let b = point_face_view_proj(light_pos, 1, 25.0);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn directional_view_proj_is_finite() {
+        let view_proj = directional_view_proj((0.0, -1.0, 0.0), (0.0, 0.0, 0.0), 10.0);
+        for col in &view_proj {
+            for v in col {
+                assert!(v.is_finite());
+            }
+        }
+    }
+}